@@ -0,0 +1,31 @@
+//! Ergonomic, level-aware logging, gated behind the `console` feature so a
+//! release build can drop the formatting and panic-hook machinery entirely.
+//!
+//! `error_doing_weird_stuff` hand-builds `JsValue`s for every `console.*`
+//! call; [`console_log!`]/[`console_error!`] do that `format_args!` ->
+//! `JsValue` conversion once so call sites read like `println!`/`eprintln!`.
+
+/// Forwards `format_args!` to `console.log`.
+#[macro_export]
+macro_rules! console_log {
+    ($($arg:tt)*) => {
+        ::web_sys::console::log_1(&format!($($arg)*).into())
+    };
+}
+
+/// Forwards `format_args!` to `console.error`.
+#[macro_export]
+macro_rules! console_error {
+    ($($arg:tt)*) => {
+        ::web_sys::console::error_1(&format!($($arg)*).into())
+    };
+}
+
+/// Installs a panic hook that reports Rust panics via `console.error` with a
+/// real stack trace (instead of the default opaque `unreachable` trap), and
+/// routes the `log` crate's records through the console. Call once, as early
+/// as possible - typically the first line of `WasmApp::new`.
+pub(crate) fn init() {
+    console_error_panic_hook::set_once();
+    wasm_logger::init(wasm_logger::Config::default());
+}
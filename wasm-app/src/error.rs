@@ -0,0 +1,108 @@
+//! A `postMessage`-safe error type.
+//!
+//! `JsError`/`js_sys::Error` don't survive the structured clone algorithm used
+//! by `MessagePort::post_message` - the receiving end just never sees them.
+//! `WorkerError` sidesteps that by carrying its fields as plain, cloneable
+//! strings and only turning itself into a `{ name, message, stack }` object
+//! (or back into a real `Error`) at the port boundary.
+
+use wasm_bindgen::prelude::*;
+
+/// An error that can cross a `MessagePort` intact.
+#[derive(Debug, Clone)]
+pub(crate) struct WorkerError {
+    name: String,
+    message: String,
+    stack: String,
+}
+
+impl WorkerError {
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        Self {
+            name: "Error".to_string(),
+            message: message.into(),
+            stack: String::new(),
+        }
+    }
+
+    /// Serializes into a plain object safe to pass to `post_message`.
+    pub(crate) fn to_js_value(&self) -> JsValue {
+        let obj = js_sys::Object::new();
+        let set = |key: &str, value: &str| js_sys::Reflect::set(&obj, &key.into(), &value.into());
+        let _ = set("name", &self.name);
+        let _ = set("message", &self.message);
+        let _ = set("stack", &self.stack);
+        obj.into()
+    }
+}
+
+impl From<JsError> for WorkerError {
+    fn from(err: JsError) -> Self {
+        Self::from(&JsValue::from(err))
+    }
+}
+
+impl From<&JsValue> for WorkerError {
+    fn from(value: &JsValue) -> Self {
+        let get_string = |key: &str| -> String {
+            js_sys::Reflect::get(value, &key.into())
+                .ok()
+                .and_then(|v| v.as_string())
+                .unwrap_or_default()
+        };
+
+        if let Some(message) = value.as_string() {
+            return Self::new(message);
+        }
+
+        Self {
+            name: {
+                let name = get_string("name");
+                if name.is_empty() { "Error".to_string() } else { name }
+            },
+            message: get_string("message"),
+            stack: get_string("stack"),
+        }
+    }
+}
+
+/// Reconstructs a JS `Error` from a `{ name, message, stack }` object that was
+/// decoded on the receiving end of a `post_error` call.
+pub(crate) fn decode(value: &JsValue) -> js_sys::Error {
+    let worker_err = WorkerError::from(value);
+    let err = js_sys::Error::new(&worker_err.message);
+    err.set_name(&worker_err.name);
+    if !worker_err.stack.is_empty() {
+        // `stack` isn't part of the `Error` spec js-sys binds, but every engine
+        // that matters treats it as a writable own property.
+        let _ = js_sys::Reflect::set(&err, &"stack".into(), &worker_err.stack.into());
+    }
+    err
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    fn to_js_value_round_trips_through_decode() {
+        let original = WorkerError::from(JsError::new("boom"));
+        let decoded = decode(&original.to_js_value());
+
+        assert_eq!(decoded.name(), "Error");
+        assert_eq!(decoded.message(), "boom");
+    }
+
+    #[wasm_bindgen_test]
+    fn decode_defaults_name_when_missing() {
+        let value = js_sys::Object::new();
+        let _ = js_sys::Reflect::set(&value, &"message".into(), &"oops".into());
+
+        let decoded = decode(&value.into());
+
+        assert_eq!(decoded.name(), "Error");
+        assert_eq!(decoded.message(), "oops");
+    }
+}
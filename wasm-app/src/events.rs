@@ -0,0 +1,153 @@
+//! Typed inbound events and the queue `WasmApp` drains them from.
+//!
+//! [`crate::rpc::Rpc`] only understands `{id, result|error}` replies;
+//! everything else arriving on the port is a fire-and-forget message from
+//! the main thread. `AppEvent` gives that side of the protocol a shape
+//! instead of the stringly-typed one-off posts the playground started with.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::MessagePort;
+
+/// How often the worker drains `EventQueue` and acts on what it finds.
+const TICK_MILLIS: i32 = 16;
+
+/// A decoded inbound message, queued until the next tick drains it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum AppEvent {
+    Ping,
+    Compute { input: f64 },
+    Shutdown,
+}
+
+/// `Arc<Mutex<..>>` rather than `Rc<RefCell<..>>`: the `onmessage` callback
+/// and the tick loop are separate JS callbacks, and a SharedArrayBuffer-backed
+/// worker pool ([`crate::pool`]) may drive them from different threads.
+pub(crate) type EventQueue = Arc<Mutex<VecDeque<AppEvent>>>;
+
+/// The tick loop's self-reference, held across reschedules so the `Closure`
+/// lives as long as the worker does.
+type TickHandle = Rc<RefCell<Option<Closure<dyn FnMut()>>>>;
+
+/// Decodes a `{event, ...}` envelope into an [`AppEvent`], if recognized.
+/// Returns `None` for anything else: `Rpc` reply envelopes, unknown event
+/// names, and a `compute` event without a numeric `input` alike, so "not an
+/// event" and "a malformed event" are not told apart.
+pub(crate) fn decode(value: &JsValue) -> Option<AppEvent> {
+    let name = js_sys::Reflect::get(value, &"event".into())
+        .ok()?
+        .as_string()?;
+
+    match name.as_str() {
+        "ping" => Some(AppEvent::Ping),
+        "compute" => {
+            let input = js_sys::Reflect::get(value, &"input".into())
+                .ok()?
+                .as_f64()?;
+            Some(AppEvent::Compute { input })
+        }
+        "shutdown" => Some(AppEvent::Shutdown),
+        _ => None,
+    }
+}
+
+/// Starts draining `queue` every [`TICK_MILLIS`] and acting on what's found,
+/// for as long as the worker is alive. Modelled as a self-rescheduling
+/// `setTimeout` rather than `setInterval` so a slow tick can't pile up
+/// overlapping drains.
+pub(crate) fn spawn_tick_loop(queue: EventQueue, port: MessagePort) {
+    let tick: TickHandle = Rc::new(RefCell::new(None));
+    let tick_for_closure = Rc::clone(&tick);
+
+    *tick.borrow_mut() = Some(Closure::new(move || {
+        drain(&queue, &port);
+        reschedule(&tick_for_closure);
+    }));
+
+    reschedule(&tick);
+
+    // The chain keeps re-scheduling itself via `tick_for_closure` for the
+    // lifetime of the worker; there is no owner left to drop it early.
+    std::mem::forget(tick);
+}
+
+fn reschedule(tick: &TickHandle) {
+    let scope: web_sys::WorkerGlobalScope = js_sys::global().unchecked_into();
+    if let Some(closure) = tick.borrow().as_ref() {
+        let _ = scope.set_timeout_with_callback_and_timeout_and_arguments_0(
+            closure.as_ref().unchecked_ref(),
+            TICK_MILLIS,
+        );
+    }
+}
+
+fn drain(queue: &EventQueue, port: &MessagePort) {
+    let events: Vec<AppEvent> = {
+        let mut queue = queue.lock().expect("event queue poisoned");
+        queue.drain(..).collect()
+    };
+
+    for event in events {
+        match event {
+            AppEvent::Ping => {
+                let _ = port.post_message(&"pong".into());
+            }
+            AppEvent::Compute { input } => {
+                let _ = port.post_message(&(input * input).into());
+            }
+            AppEvent::Shutdown => {
+                web_sys::console::info_1(&"WasmApp: shutdown event received".into());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn envelope(pairs: &[(&str, JsValue)]) -> JsValue {
+        let obj = js_sys::Object::new();
+        for (key, value) in pairs {
+            let _ = js_sys::Reflect::set(&obj, &(*key).into(), value);
+        }
+        obj.into()
+    }
+
+    #[wasm_bindgen_test]
+    fn decodes_ping() {
+        let value = envelope(&[("event", "ping".into())]);
+        assert_eq!(decode(&value), Some(AppEvent::Ping));
+    }
+
+    #[wasm_bindgen_test]
+    fn decodes_compute_with_input() {
+        let value = envelope(&[("event", "compute".into()), ("input", 4.0.into())]);
+        assert_eq!(decode(&value), Some(AppEvent::Compute { input: 4.0 }));
+    }
+
+    #[wasm_bindgen_test]
+    fn decodes_shutdown() {
+        let value = envelope(&[("event", "shutdown".into())]);
+        assert_eq!(decode(&value), Some(AppEvent::Shutdown));
+    }
+
+    #[wasm_bindgen_test]
+    fn rejects_unknown_event_name() {
+        let value = envelope(&[("event", "nonsense".into())]);
+        assert_eq!(decode(&value), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn rejects_reply_envelope_without_event_field() {
+        let value = envelope(&[("id", 0.0.into()), ("result", "pong".into())]);
+        assert_eq!(decode(&value), None);
+    }
+}
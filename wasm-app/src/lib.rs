@@ -1,9 +1,28 @@
+#[cfg(feature = "console")]
+mod console;
+mod error;
+mod events;
+#[cfg(feature = "parallel")]
+pub mod pool;
+mod rpc;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use js_sys::Promise;
 use wasm_bindgen::prelude::*;
-use web_sys::MessagePort;
+use wasm_bindgen::JsCast;
+use web_sys::{MessageEvent, MessagePort};
+
+use error::WorkerError;
+use events::EventQueue;
+use rpc::Rpc;
 
 #[wasm_bindgen]
 pub struct WasmApp {
-    _port: MessagePort,
+    rpc: Rpc,
+    // Kept alive for as long as `WasmApp` is alive; dropping it would unhook `onmessage`.
+    _onmessage: Closure<dyn FnMut(MessageEvent)>,
 }
 
 #[wasm_bindgen]
@@ -11,20 +30,86 @@ pub struct WasmApp {
 impl WasmApp {
     #[wasm_bindgen(constructor)]
     pub fn new(port: MessagePort) -> Self {
+        #[cfg(feature = "console")]
+        console::init();
+
         error_doing_weird_stuff();
 
-        let err1 = JsError::new("JsError inside worker, rust");
-        // doesn't seem to be sent at all?
-        port.post_message(&err1.into()).expect("sent message");
+        let rpc = Rpc::new(port.clone());
+        rpc.register("ping", |_params| Ok(JsValue::from_str("pong")));
+        rpc.register("compute", |params| {
+            let input = params
+                .as_f64()
+                .ok_or_else(|| JsValue::from(JsError::new("\"compute\" expects a number")))?;
+            Ok((input * input).into())
+        });
+
+        let queue: EventQueue = Arc::new(Mutex::new(VecDeque::new()));
+
+        let onmessage = {
+            let pending = rpc.pending_handle();
+            let handlers = rpc.handlers_handle();
+            let reply_port = port.clone();
+            let queue = Arc::clone(&queue);
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                // A reply to one of our own calls takes priority; then a
+                // request for one of our handlers; anything else left over
+                // is a fire-and-forget event for the tick loop to drain.
+                if rpc::handle_reply(&pending, &event) {
+                    return;
+                }
+                if rpc::handle_request(&handlers, &reply_port, &event) {
+                    return;
+                }
+                if let Some(app_event) = events::decode(&event.data()) {
+                    queue.lock().expect("event queue poisoned").push_back(app_event);
+                }
+            })
+        };
+        port.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
 
-        // is sent successfully, so above does not panic
-        port.post_message(&"Hello, World".into())
+        events::spawn_tick_loop(queue, port.clone());
+
+        let app = Self { rpc, _onmessage: onmessage };
+
+        // JsError/Error don't survive structured clone, so this has to go out
+        // as a WorkerError instead of the raw JsError the old code tried here.
+        app.post_error(WorkerError::from(JsError::new("JsError inside worker, rust")));
+
+        app.rpc
+            .post(&"Hello, World".into())
             .expect("sent message");
 
-        Self { _port: port }
+        app
+    }
+
+    /// Sends `method`/`params` to the other end of the port and returns a promise that
+    /// resolves with its reply, e.g. `await app.call("eval", args)`.
+    pub fn call(&self, method: &str, params: JsValue) -> Promise {
+        self.rpc.call(method, params)
     }
 }
 
+impl WasmApp {
+    /// Posts `err` to the main thread as a plain, cloneable object so it
+    /// actually survives the trip across the `MessagePort`.
+    pub(crate) fn post_error(&self, err: WorkerError) {
+        self.rpc
+            .post(&err.to_js_value())
+            .expect("sent message");
+    }
+}
+
+#[cfg(feature = "console")]
+fn error_doing_weird_stuff() {
+    let err0 = JsError::new("JsError inside worker, rust");
+    console_log!("<Expecting JsError>");
+    // doesn't seem to be printed ??
+    console_error!("JsError inside worker: {:?}", JsValue::from(err0));
+    console_log!("</Expecting JsError>");
+}
+
+#[cfg(not(feature = "console"))]
 fn error_doing_weird_stuff() {
     let err0 = JsError::new("JsError inside worker, rust");
     web_sys::console::info_1(&"<Expecting JsError>".into());
@@ -0,0 +1,201 @@
+//! A small `wasm-bindgen-rayon`-style worker pool for data-parallel work.
+//!
+//! Where [`crate::rpc::Rpc`] and [`crate::events`] talk to a single,
+//! externally-owned port, `WorkerPool` owns its workers outright: it spawns
+//! `size` dedicated workers that all load this module against the same
+//! `SharedArrayBuffer`-backed memory, then hands each `execute` job to an
+//! idle one as a raw pointer the worker calls back into via
+//! [`child_entry_point`].
+
+#[cfg(not(all(target_feature = "atomics", target_feature = "bulk-memory")))]
+compile_error!(
+    "crate::pool requires `RUSTFLAGS=\"-C target-feature=+atomics,+bulk-memory\"` and a \
+     nightly `-Z build-std` (see wasm-bindgen-rayon) - without shared memory, workers can't \
+     see the same wasm heap as the main thread."
+);
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use futures::channel::oneshot;
+use js_sys::{Array, Promise};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{DedicatedWorkerGlobalScope, MessageEvent, Worker, WorkerOptions, WorkerType};
+
+use crate::error::WorkerError;
+
+/// Type-erased work handed across to a worker as a raw pointer; reconstructed
+/// and run by [`child_entry_point`].
+type Job = Box<dyn FnOnce() + Send>;
+
+/// One spawned worker plus the bookkeeping needed to dispatch a job to it and
+/// learn when that job finishes.
+struct WorkerSlot {
+    worker: Worker,
+    busy: Rc<Cell<bool>>,
+    completion: Rc<RefCell<Option<oneshot::Sender<Result<JsValue, JsValue>>>>>,
+    // Kept alive for as long as the slot is alive; dropping it would unhook `onmessage`.
+    _onmessage: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl WorkerSlot {
+    fn spawn() -> Result<Self, JsValue> {
+        let opts = WorkerOptions::new();
+        opts.set_type(WorkerType::Module);
+        let worker = Worker::new_with_options("./workerHelpers.js", &opts)?;
+
+        let completion: Rc<RefCell<Option<oneshot::Sender<Result<JsValue, JsValue>>>>> =
+            Rc::new(RefCell::new(None));
+
+        let onmessage = {
+            let completion = Rc::clone(&completion);
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                let Some(sender) = completion.borrow_mut().take() else {
+                    // No job in flight on this worker - not our message.
+                    return;
+                };
+                let data = event.data();
+                let error = js_sys::Reflect::get(&data, &"error".into()).unwrap_or(JsValue::UNDEFINED);
+                let result = if error.is_undefined() { Ok(data) } else { Err(error) };
+                let _ = sender.send(result);
+            })
+        };
+        worker.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+
+        // Hand the worker this module's compiled bytes and shared memory so it
+        // can instantiate the same wasm instance before taking any jobs - the
+        // bootstrap dance `wasm-bindgen-rayon` does in `workerHelpers.js`.
+        let init = Array::of2(&wasm_bindgen::module(), &wasm_bindgen::memory());
+        worker.post_message(&init)?;
+
+        Ok(Self {
+            worker,
+            busy: Rc::new(Cell::new(false)),
+            completion,
+            _onmessage: onmessage,
+        })
+    }
+
+    /// Posts `ptr` (a [`Job`] pointer) to the worker and returns a promise
+    /// that settles once it reports `child_entry_point` finished.
+    fn dispatch(&self, ptr: u32) -> Promise {
+        let (tx, rx) = oneshot::channel();
+        *self.completion.borrow_mut() = Some(tx);
+
+        let posted = self.worker.post_message(&ptr.into());
+
+        wasm_bindgen_futures::future_to_promise(async move {
+            posted?;
+            match rx.await {
+                Ok(result) => result,
+                Err(_) => Err(JsError::new("worker dropped before reporting completion").into()),
+            }
+        })
+    }
+}
+
+/// A fixed-size set of workers sharing this module's wasm memory.
+pub struct WorkerPool {
+    slots: Rc<RefCell<Vec<WorkerSlot>>>,
+    // Promises for jobs dispatched since the last `join`.
+    inflight: Rc<RefCell<Vec<Promise>>>,
+}
+
+impl WorkerPool {
+    /// Spawns `size` workers, loaded with the current module/memory so they
+    /// can run jobs against the same shared heap as the main thread.
+    pub fn new(size: usize) -> Result<Self, JsValue> {
+        let mut slots = Vec::with_capacity(size);
+        for _ in 0..size {
+            slots.push(WorkerSlot::spawn()?);
+        }
+        Ok(Self {
+            slots: Rc::new(RefCell::new(slots)),
+            inflight: Rc::new(RefCell::new(Vec::new())),
+        })
+    }
+
+    /// Number of workers not currently running a job.
+    pub fn idle_count(&self) -> usize {
+        self.slots.borrow().iter().filter(|slot| !slot.busy.get()).count()
+    }
+
+    /// Reserves an idle worker's index, marking it busy. `None` if the pool
+    /// is saturated - callers should queue and retry rather than oversubscribe.
+    fn reserve(&self) -> Option<usize> {
+        let slots = self.slots.borrow();
+        let index = slots.iter().position(|slot| !slot.busy.get())?;
+        slots[index].busy.set(true);
+        Some(index)
+    }
+
+    /// Runs `job` on the next idle worker. The returned promise resolves once
+    /// that worker reports completion, and rejects immediately if every
+    /// worker is currently busy.
+    pub fn execute(&self, job: impl FnOnce() + Send + 'static) -> Promise {
+        let Some(index) = self.reserve() else {
+            return wasm_bindgen_futures::future_to_promise(async {
+                Err(JsError::new("no idle worker available").into())
+            });
+        };
+
+        let job: Job = Box::new(job);
+        let ptr = Box::into_raw(Box::new(job)) as u32;
+
+        let slots = Rc::clone(&self.slots);
+        let promise = wasm_bindgen_futures::future_to_promise(async move {
+            let dispatched = slots.borrow()[index].dispatch(ptr);
+            let result = JsFuture::from(dispatched).await;
+            slots.borrow()[index].busy.set(false);
+            result
+        });
+
+        self.inflight.borrow_mut().push(promise.clone());
+        promise
+    }
+
+    /// A promise that resolves once every job dispatched since the last
+    /// `join` has completed - the pool's equivalent of `rayon::join`/a
+    /// thread-pool barrier.
+    pub fn join(&self) -> Promise {
+        let pending: Array = self.inflight.borrow_mut().drain(..).collect();
+        wasm_bindgen_futures::future_to_promise(async move {
+            JsFuture::from(Promise::all(&pending)).await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+}
+
+/// Called by each worker's bootstrap script once it has instantiated the
+/// shared module; reconstructs and runs the [`Job`] at `ptr`, then posts a
+/// `{}`/`{error}` completion message back so [`WorkerSlot::dispatch`]'s
+/// promise (and therefore `execute()`/`join()`) can settle.
+#[wasm_bindgen]
+pub fn child_entry_point(ptr: u32) {
+    let job = unsafe { Box::from_raw(ptr as *mut Job) };
+    let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+
+    let reply = js_sys::Object::new();
+    if let Err(payload) = outcome {
+        let encoded = WorkerError::new(panic_message(&payload)).to_js_value();
+        let _ = js_sys::Reflect::set(&reply, &"error".into(), &encoded);
+    }
+
+    let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+    let _ = scope.post_message(&reply);
+}
+
+/// Extracts a human-readable message from a `catch_unwind` payload, the way
+/// `std`'s default panic hook does (`&str`/`String`, falling back to a
+/// generic message for anything else `panic_any` might have thrown).
+fn panic_message(payload: &Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "job panicked with a non-string payload".to_string()
+    }
+}
@@ -0,0 +1,294 @@
+//! Request/response correlation on top of a raw `MessagePort`.
+//!
+//! The port itself only knows how to shuttle `JsValue`s back and forth; `Rpc`
+//! layers a `{id, method, params}` / `{id, result}` / `{id, error}` envelope
+//! convention on top of that so a caller can `await` a specific reply instead
+//! of guessing which `onmessage` event belongs to which call.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use futures::channel::oneshot;
+use js_sys::Promise;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{MessageEvent, MessagePort};
+
+use crate::error::{self, WorkerError};
+
+pub(crate) type PendingReplies = Rc<RefCell<HashMap<u32, oneshot::Sender<Result<JsValue, JsValue>>>>>;
+
+/// A registered responder for an inbound `{id, method, params}` request.
+pub(crate) type Handler = Box<dyn Fn(JsValue) -> Result<JsValue, JsValue>>;
+
+type Handlers = Rc<RefCell<HashMap<String, Handler>>>;
+
+/// Assigns monotonically increasing ids to outgoing calls and resolves the
+/// matching promise once a `{id, ...}` reply comes back over the port; also
+/// holds the handler registry that answers the other side's `{id, method,
+/// params}` requests.
+///
+/// `Rpc` does not listen on the port itself - incoming messages can also be
+/// [`crate::events::AppEvent`]s, so the caller owns the single `onmessage`
+/// dispatcher and feeds each message to [`handle_reply`] then
+/// [`handle_request`]. `Rpc` does, however, own the port's `messageerror`
+/// handler and its own teardown, since those always mean "every outstanding
+/// call just lost its reply".
+pub(crate) struct Rpc {
+    port: MessagePort,
+    next_id: Rc<Cell<u32>>,
+    pending: PendingReplies,
+    handlers: Handlers,
+    // Kept alive for as long as `Rpc` is alive; dropping it would unhook `onmessageerror`.
+    _onmessageerror: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl Rpc {
+    pub(crate) fn new(port: MessagePort) -> Self {
+        let pending: PendingReplies = Rc::new(RefCell::new(HashMap::new()));
+
+        // There's no portable "port closed" event, but a message that fails
+        // to deserialize on the other end is the closest the Web platform
+        // gets, and `Drop` below covers the Rust-side teardown case.
+        let onmessageerror = {
+            let pending = Rc::clone(&pending);
+            Closure::<dyn FnMut(MessageEvent)>::new(move |_event: MessageEvent| {
+                reject_all(&pending, "message port reported an error");
+            })
+        };
+        port.set_onmessageerror(Some(onmessageerror.as_ref().unchecked_ref()));
+
+        Self {
+            port,
+            next_id: Rc::new(Cell::new(0)),
+            pending,
+            handlers: Rc::new(RefCell::new(HashMap::new())),
+            _onmessageerror: onmessageerror,
+        }
+    }
+
+    /// A clone of the pending-replies handle, for the shared `onmessage`
+    /// dispatcher to hand to [`handle_reply`].
+    pub(crate) fn pending_handle(&self) -> PendingReplies {
+        Rc::clone(&self.pending)
+    }
+
+    /// A clone of the handler registry, for the shared `onmessage` dispatcher
+    /// to hand to [`handle_request`].
+    pub(crate) fn handlers_handle(&self) -> Handlers {
+        Rc::clone(&self.handlers)
+    }
+
+    /// Registers `handler` to answer inbound `{id, method: name, params}`
+    /// requests. Registering the same `name` twice replaces the old handler.
+    pub(crate) fn register(
+        &self,
+        name: impl Into<String>,
+        handler: impl Fn(JsValue) -> Result<JsValue, JsValue> + 'static,
+    ) {
+        self.handlers.borrow_mut().insert(name.into(), Box::new(handler));
+    }
+
+    /// Posts an already-encoded value on the port as-is, bypassing the
+    /// request/reply envelope (e.g. for out-of-band error notifications).
+    pub(crate) fn post(&self, value: &JsValue) -> Result<(), JsValue> {
+        self.port.post_message(value)
+    }
+
+    /// Posts `{id, method, params}` on the port and returns a promise that
+    /// settles when the matching `{id, result}`/`{id, error}` reply arrives.
+    pub(crate) fn call(&self, method: &str, params: JsValue) -> Promise {
+        let id = self.next_id.get();
+        self.next_id.set(id.wrapping_add(1));
+
+        let (tx, rx) = oneshot::channel();
+        self.pending.borrow_mut().insert(id, tx);
+
+        let envelope = js_sys::Object::new();
+        let set = |key: &str, value: JsValue| js_sys::Reflect::set(&envelope, &key.into(), &value);
+        let _ = set("id", id.into());
+        let _ = set("method", method.into());
+        let _ = set("params", params);
+
+        if let Err(err) = self.port.post_message(&envelope) {
+            self.pending.borrow_mut().remove(&id);
+            return wasm_bindgen_futures::future_to_promise(async move { Err(err) });
+        }
+
+        let pending = Rc::clone(&self.pending);
+        wasm_bindgen_futures::future_to_promise(async move {
+            match rx.await {
+                Ok(result) => result,
+                Err(_) => {
+                    // The port was dropped (or the app shut down) before a reply arrived.
+                    pending.borrow_mut().remove(&id);
+                    Err(JsError::new("message port closed before a reply arrived").into())
+                }
+            }
+        })
+    }
+}
+
+impl Drop for Rpc {
+    fn drop(&mut self) {
+        // The port is going away with us; nothing will ever deliver a reply
+        // to whatever is still waiting, so reject it instead of hanging.
+        reject_all(&self.pending, "message port closed");
+    }
+}
+
+/// Drains every pending call and rejects its promise with `reason`.
+fn reject_all(pending: &PendingReplies, reason: &str) {
+    for (_, sender) in pending.borrow_mut().drain() {
+        let _ = sender.send(Err(JsError::new(reason).into()));
+    }
+}
+
+/// Tries to treat `event` as a `{id, result|error}` reply to a pending
+/// [`Rpc::call`]. Returns `false` (consuming nothing) if `event` isn't a
+/// reply, or replies to an id nothing is waiting on, so the caller can fall
+/// back to treating it as an [`crate::events::AppEvent`] instead.
+pub(crate) fn handle_reply(pending: &PendingReplies, event: &MessageEvent) -> bool {
+    let data = event.data();
+
+    let Some(id) = js_sys::Reflect::get(&data, &"id".into())
+        .ok()
+        .and_then(|id| id.as_f64())
+    else {
+        return false;
+    };
+
+    // A `{id, method, params}` request from the other side is not a reply,
+    // even when its id happens to match one of our own pending calls.
+    if js_sys::Reflect::has(&data, &"method".into()).unwrap_or(false) {
+        return false;
+    }
+
+    let Some(sender) = pending.borrow_mut().remove(&(id as u32)) else {
+        // No in-flight call with this id: a duplicate reply, or an unrelated message. Ignore it.
+        return false;
+    };
+
+    let error = js_sys::Reflect::get(&data, &"error".into()).unwrap_or(JsValue::UNDEFINED);
+    let result = if error.is_undefined() {
+        Ok(js_sys::Reflect::get(&data, &"result".into()).unwrap_or(JsValue::UNDEFINED))
+    } else {
+        // Reconstruct a real `Error` (name/message/stack) from the
+        // `WorkerError`-shaped envelope instead of handing back a plain object.
+        Err(error::decode(&error).into())
+    };
+
+    let _ = sender.send(result);
+    true
+}
+
+/// Tries to treat `event` as a `{id, method, params}` request: looks up
+/// `method` in `handlers`, runs it, and posts `{id, result}`/`{id, error}`
+/// back on `port`. Returns `false` (posting nothing) if `event` has no
+/// `method` field, so the caller can fall back to treating it as an
+/// [`crate::events::AppEvent`] instead.
+pub(crate) fn handle_request(handlers: &Handlers, port: &MessagePort, event: &MessageEvent) -> bool {
+    let data = event.data();
+
+    let Some(method) = js_sys::Reflect::get(&data, &"method".into())
+        .ok()
+        .and_then(|method| method.as_string())
+    else {
+        return false;
+    };
+
+    let id = js_sys::Reflect::get(&data, &"id".into()).ok().and_then(|id| id.as_f64());
+    let params = js_sys::Reflect::get(&data, &"params".into()).unwrap_or(JsValue::UNDEFINED);
+
+    let result = match handlers.borrow().get(method.as_str()) {
+        Some(handler) => handler(params),
+        None => Err(JsError::new(&format!("no handler registered for \"{method}\"")).into()),
+    };
+
+    if let Some(id) = id {
+        let reply = js_sys::Object::new();
+        let _ = js_sys::Reflect::set(&reply, &"id".into(), &id.into());
+        match result {
+            Ok(value) => {
+                let _ = js_sys::Reflect::set(&reply, &"result".into(), &value);
+            }
+            Err(err) => {
+                let encoded = WorkerError::from(&err).to_js_value();
+                let _ = js_sys::Reflect::set(&reply, &"error".into(), &encoded);
+            }
+        }
+        let _ = port.post_message(&reply);
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use web_sys::MessageEventInit;
+
+    fn message_event(data: &JsValue) -> MessageEvent {
+        let init = MessageEventInit::new();
+        init.set_data(data);
+        MessageEvent::new_with_event_init_dict("message", &init).expect("constructed MessageEvent")
+    }
+
+    fn envelope(pairs: &[(&str, JsValue)]) -> JsValue {
+        let obj = js_sys::Object::new();
+        for (key, value) in pairs {
+            let _ = js_sys::Reflect::set(&obj, &(*key).into(), value);
+        }
+        obj.into()
+    }
+
+    #[wasm_bindgen_test]
+    fn handle_reply_ignores_envelopes_without_id() {
+        let pending: PendingReplies = Rc::new(RefCell::new(HashMap::new()));
+        let event = message_event(&envelope(&[("result", "pong".into())]));
+
+        assert!(!handle_reply(&pending, &event));
+    }
+
+    #[wasm_bindgen_test]
+    fn handle_reply_ignores_unknown_id() {
+        let pending: PendingReplies = Rc::new(RefCell::new(HashMap::new()));
+        let event = message_event(&envelope(&[("id", 0.0.into()), ("result", "pong".into())]));
+
+        assert!(!handle_reply(&pending, &event));
+    }
+
+    #[wasm_bindgen_test]
+    fn handle_reply_resolves_matching_pending_call() {
+        let pending: PendingReplies = Rc::new(RefCell::new(HashMap::new()));
+        let (tx, mut rx) = oneshot::channel();
+        pending.borrow_mut().insert(0, tx);
+
+        let event = message_event(&envelope(&[("id", 0.0.into()), ("result", "pong".into())]));
+        assert!(handle_reply(&pending, &event));
+
+        let result = rx.try_recv().expect("reply delivered").expect("reply present");
+        assert_eq!(result.as_ref().unwrap().as_string().as_deref(), Some("pong"));
+    }
+
+    #[wasm_bindgen_test]
+    fn handle_request_claims_request_for_missing_handler() {
+        let handlers: Handlers = Rc::new(RefCell::new(HashMap::new()));
+        let port = web_sys::MessageChannel::new().expect("constructed MessageChannel").port1();
+
+        let event = message_event(&envelope(&[("id", 0.0.into()), ("method", "ping".into())]));
+        assert!(handle_request(&handlers, &port, &event));
+    }
+
+    #[wasm_bindgen_test]
+    fn handle_request_ignores_envelopes_without_method() {
+        let handlers: Handlers = Rc::new(RefCell::new(HashMap::new()));
+        let port = web_sys::MessageChannel::new().expect("constructed MessageChannel").port1();
+
+        let event = message_event(&envelope(&[("id", 0.0.into()), ("result", "pong".into())]));
+        assert!(!handle_request(&handlers, &port, &event));
+    }
+}